@@ -1,7 +1,7 @@
 //! Schema verification for TCS
 
-use std::collections::{HashMap, HashSet};
-use tcs_schema::{Definition, DefinitionKind, Schema};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tcs_schema::{Definition, Schema};
 
 use crate::error::TcsError;
 use crate::utils::quote;
@@ -14,34 +14,43 @@ pub const NATIVE_TYPES: &[&str] = &[
     "bool", "byte", "int", "uint", "float", "string", "int64", "uint64",
 ];
 
-/// Verify a schema for correctness
+/// Verify a schema for correctness, returning only the first problem found.
+///
+/// This is a thin wrapper around [`verify_schema_all`] for callers that only
+/// care about whether the schema is valid, not the full list of problems.
 pub fn verify_schema(schema: &Schema) -> Result<(), TcsError> {
+    verify_schema_all(schema).map_err(|mut errors| errors.remove(0))
+}
+
+/// Verify a schema for correctness, collecting every problem found instead of
+/// stopping at the first one.
+pub fn verify_schema_all(schema: &Schema) -> Result<(), Vec<TcsError>> {
+    let mut errors: Vec<TcsError> = Vec::new();
     let mut defined_types: HashSet<String> = NATIVE_TYPES.iter().map(|s| s.to_string()).collect();
     let mut definitions_map: HashMap<String, &Definition> = HashMap::new();
 
     // 1) Check duplicate / reserved type names
     for def in &schema.definitions {
         if defined_types.contains(&def.name) {
-            return Err(TcsError::VerificationError(format!(
+            errors.push(TcsError::VerificationError(format!(
                 "The type {} is defined twice",
                 quote(&def.name)
             )));
+        } else {
+            defined_types.insert(def.name.clone());
         }
         if RESERVED_NAMES.contains(&def.name.as_str()) {
-            return Err(TcsError::VerificationError(format!(
+            errors.push(TcsError::VerificationError(format!(
                 "The type name {} is reserved",
                 quote(&def.name)
             )));
         }
-        defined_types.insert(def.name.clone());
-        definitions_map.insert(def.name.clone(), def);
+        definitions_map.entry(def.name.clone()).or_insert(def);
     }
 
-    // 2) Check fields inside each non-enum definition
+    // 2) Check fields inside each definition (struct fields and enum variants
+    // go through the same checks: both are represented as `Field`s)
     for def in &schema.definitions {
-        if let DefinitionKind::Enum = def.kind {
-            continue;
-        }
         if def.fields.is_empty() {
             continue;
         }
@@ -50,7 +59,7 @@ pub fn verify_schema(schema: &Schema) -> Result<(), TcsError> {
         for field in &def.fields {
             if let Some(ref ty) = field.type_ {
                 if !defined_types.contains(ty) {
-                    return Err(TcsError::VerificationError(format!(
+                    errors.push(TcsError::VerificationError(format!(
                         "The type {} is not defined for field {}",
                         quote(ty),
                         quote(&field.name)
@@ -61,14 +70,14 @@ pub fn verify_schema(schema: &Schema) -> Result<(), TcsError> {
             // Check that fixed-size arrays only use byte type
             if let Some(size) = field.array_size {
                 if field.type_.as_deref() != Some("byte") {
-                    return Err(TcsError::VerificationError(format!(
+                    errors.push(TcsError::VerificationError(format!(
                         "Fixed-size arrays are only supported for byte type, not {} in field {}",
                         quote(field.type_.as_deref().unwrap_or("unknown")),
                         quote(&field.name)
                     )));
                 }
                 if size == 0 {
-                    return Err(TcsError::VerificationError(format!(
+                    errors.push(TcsError::VerificationError(format!(
                         "Fixed-size array cannot have size 0 in field {}",
                         quote(&field.name)
                     )));
@@ -80,19 +89,19 @@ pub fn verify_schema(schema: &Schema) -> Result<(), TcsError> {
         let mut values = HashSet::new();
         for field in &def.fields {
             if values.contains(&field.field_id) {
-                return Err(TcsError::VerificationError(format!(
+                errors.push(TcsError::VerificationError(format!(
                     "The id for field {} is used twice",
                     quote(&field.name)
                 )));
             }
             if field.field_id <= 0 {
-                return Err(TcsError::VerificationError(format!(
+                errors.push(TcsError::VerificationError(format!(
                     "The id for field {} must be positive",
                     quote(&field.name)
                 )));
             }
             if field.field_id > def.fields.len() as i32 {
-                return Err(TcsError::VerificationError(format!(
+                errors.push(TcsError::VerificationError(format!(
                     "The id for field {} cannot be larger than {}",
                     quote(&field.name),
                     def.fields.len()
@@ -102,48 +111,109 @@ pub fn verify_schema(schema: &Schema) -> Result<(), TcsError> {
         }
     }
 
-    // 3) Check that structs do not contain themselves recursively
-    let mut state: HashMap<String, u8> = HashMap::new();
+    // 3) Check that structs and enums do not structurally contain themselves
+    //
+    // We track the current DFS chain as an explicit path stack rather than a
+    // coloring map, so that when we re-encounter a type already on the stack
+    // we can slice out the exact cycle instead of just naming the node where
+    // it was detected. `verified` records types whose entire subtree came
+    // back clean, so shared non-cyclic subtrees are still only walked once
+    // (an acyclic schema is checked in O(V+E)); types that reach a cycle are
+    // never marked verified and are walked again from every path that
+    // reaches them. Enum payloads participate in the graph
+    // just like struct fields: a cycle that passes through an enum variant
+    // (struct -> enum -> struct) is just as unrepresentable as one that stays
+    // within structs.
+    //
+    // Unlike the checks above, we don't abort the whole traversal the moment
+    // one cycle is found: we record it (deduplicated by its normalized set of
+    // members, so the same cycle reached from different starting points is
+    // only reported once) and keep walking so independent cycles elsewhere in
+    // the schema are reported together.
+    let mut path: Vec<String> = Vec::new();
+    let mut verified: HashSet<String> = HashSet::new();
+    let mut seen_cycles: HashSet<BTreeSet<String>> = HashSet::new();
 
     fn check_recursion(
         name: &str,
         definitions_map: &HashMap<String, &Definition>,
-        state: &mut HashMap<String, u8>,
-    ) -> Result<(), TcsError> {
+        path: &mut Vec<String>,
+        verified: &mut HashSet<String>,
+        seen_cycles: &mut HashSet<BTreeSet<String>>,
+        errors: &mut Vec<TcsError>,
+    ) -> bool {
         let definition = match definitions_map.get(name) {
             Some(def) => def,
-            None => return Ok(()),
+            None => return true,
         };
-        if let DefinitionKind::Struct = definition.kind {
-            if let Some(&s) = state.get(name) {
-                if s == 1 {
-                    return Err(TcsError::VerificationError(format!(
-                        "Recursive nesting of {} is not allowed",
-                        quote(name)
-                    )));
-                } else if s == 2 {
-                    return Ok(());
-                }
+        if verified.contains(name) {
+            return true;
+        }
+        if let Some(pos) = path.iter().position(|n| n == name) {
+            // `name` is already on the stack: slice from there to the top to
+            // recover the exact cycle, e.g. A -> B -> C -> A.
+            let cycle = &path[pos..];
+            if seen_cycles.insert(cycle.iter().cloned().collect()) {
+                let mut chain: Vec<&str> = cycle.iter().map(|s| s.as_str()).collect();
+                chain.push(name);
+                let chain_str = chain.join(" -> ");
+                errors.push(TcsError::VerificationError(if cycle.len() == 1 {
+                    format!(
+                        "Recursive nesting of {} is not allowed (self-recursive: {})",
+                        quote(name),
+                        chain_str
+                    )
+                } else {
+                    format!(
+                        "Recursive nesting of {} is not allowed (mutually recursive through {})",
+                        quote(name),
+                        chain_str
+                    )
+                }));
             }
-            state.insert(name.to_string(), 1);
-            for field in &definition.fields {
-                // Arrays are allowed to be recursive (they break the recursion)
-                if !field.is_array {
-                    if let Some(ref ty) = field.type_ {
-                        check_recursion(ty, definitions_map, state)?;
-                    }
+            return false;
+        }
+
+        path.push(name.to_string());
+        let mut clean = true;
+        for field in &definition.fields {
+            // Arrays are allowed to be recursive (they break the recursion)
+            if !field.is_array {
+                if let Some(ref ty) = field.type_ {
+                    clean &= check_recursion(ty, definitions_map, path, verified, seen_cycles, errors);
                 }
             }
-            state.insert(name.to_string(), 2);
         }
-        Ok(())
+        path.pop();
+        // Only mark `name` verified if its whole subtree came back clean. A
+        // node that sits on a cycle reached through one ancestor may still
+        // have other, not-yet-discovered cycles reachable through a
+        // different ancestor (e.g. two siblings that both point at the same
+        // child, which itself points back to their shared parent); marking
+        // it verified unconditionally would make the second ancestor's walk
+        // skip straight past it and miss that cycle.
+        if clean {
+            verified.insert(name.to_string());
+        }
+        clean
     }
 
     for def in &schema.definitions {
-        check_recursion(&def.name, &definitions_map, &mut state)?;
+        check_recursion(
+            &def.name,
+            &definitions_map,
+            &mut path,
+            &mut verified,
+            &mut seen_cycles,
+            &mut errors,
+        );
     }
 
-    Ok(())
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
 #[cfg(test)]
@@ -158,6 +228,12 @@ mod tests {
         verify_schema(&schema)
     }
 
+    fn verify_all(input: &str) -> Result<(), Vec<TcsError>> {
+        let tokens = tokenize_schema(input).map_err(|e| vec![e])?;
+        let schema = parse_schema(&tokens).map_err(|e| vec![e])?;
+        verify_schema_all(&schema)
+    }
+
     #[test]
     fn test_valid_schema() {
         let input = r#"
@@ -190,6 +266,17 @@ mod tests {
         assert!(matches!(err, TcsError::VerificationError(_)));
     }
 
+    #[test]
+    fn test_undefined_type_in_enum_variant() {
+        let input = r#"
+            enum Shape {
+                Bogus x;
+            }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert!(matches!(err, TcsError::VerificationError(_)));
+    }
+
     #[test]
     fn test_fixed_array_only_byte() {
         let input = r#"
@@ -210,4 +297,107 @@ mod tests {
         "#;
         assert!(verify(input).is_ok());
     }
+
+    #[test]
+    fn test_self_recursive_struct() {
+        let input = r#"
+            struct Node {
+                Node next;
+            }
+        "#;
+        let err = verify(input).unwrap_err();
+        let TcsError::VerificationError(msg) = err else {
+            panic!("expected a verification error");
+        };
+        assert!(msg.contains("self-recursive"));
+        assert!(msg.contains("Node -> Node"));
+    }
+
+    #[test]
+    fn test_mutually_recursive_structs() {
+        let input = r#"
+            struct A { B b; }
+            struct B { C c; }
+            struct C { A a; }
+        "#;
+        let err = verify(input).unwrap_err();
+        let TcsError::VerificationError(msg) = err else {
+            panic!("expected a verification error");
+        };
+        assert!(msg.contains("mutually recursive"));
+        assert!(msg.contains("A -> B -> C -> A"));
+    }
+
+    #[test]
+    fn test_enum_payload_closes_cycle() {
+        let input = r#"
+            struct Node {
+                Shape payload;
+            }
+            enum Shape {
+                Node branch;
+            }
+        "#;
+        let err = verify(input).unwrap_err();
+        let TcsError::VerificationError(msg) = err else {
+            panic!("expected a verification error");
+        };
+        assert!(msg.contains("mutually recursive"));
+        assert!(msg.contains("Node -> Shape -> Node"));
+    }
+
+    #[test]
+    fn test_enum_payload_without_cycle_is_ok() {
+        let input = r#"
+            struct Leaf {
+                int value;
+            }
+            enum Shape {
+                Leaf leaf;
+            }
+        "#;
+        assert!(verify(input).is_ok());
+    }
+
+    #[test]
+    fn test_verify_schema_all_collects_every_error() {
+        let input = r#"
+            struct Foo { int x; }
+            struct Foo { int y; }
+            struct Bar {
+                Unknown z;
+            }
+        "#;
+        let errors = verify_all(input).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        for err in &errors {
+            assert!(matches!(err, TcsError::VerificationError(_)));
+        }
+    }
+
+    #[test]
+    fn test_verify_schema_all_reports_independent_cycles_once_each() {
+        let input = r#"
+            struct A { A a; }
+            struct B { B b; }
+        "#;
+        let errors = verify_all(input).unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_verify_schema_all_reports_cycles_through_shared_node() {
+        // Q1 and Q2 both route through Mid back to P, so there are two
+        // distinct cycles (P -> Q1 -> Mid -> P and P -> Q2 -> Mid -> P) that
+        // happen to share the Mid node. Marking Mid verified after the first
+        // cycle is found must not prevent the second from being discovered.
+        let input = r#"
+            struct P { Q1 q1; Q2 q2; }
+            struct Q1 { Mid m; }
+            struct Q2 { Mid m; }
+            struct Mid { P back; }
+        "#;
+        let errors = verify_all(input).unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
 }